@@ -0,0 +1,144 @@
+//! Equirectangular panorama to cubemap conversion, so a single 360 panorama can feed
+//! `TileCreator` the same way a set of pre-cut cube faces does.
+
+use image::{DynamicImage, GenericImageView, Rgba};
+
+/// Face letters used for output file prefixes, in the order faces are generated
+pub const FACE_NAMES: [&str; 6] = ["f", "r", "b", "l", "u", "d"];
+
+#[derive(Clone, Copy)]
+struct Vec3 {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Vec3 {
+    fn normalize(self) -> Self {
+        let len = (self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        Vec3 { x: self.x / len, y: self.y / len, z: self.z / len }
+    }
+}
+
+/// Direction vector piercing the cube face plane at normalized face-plane coordinates `(a, b)`,
+/// each in `[-1, 1]`
+fn face_direction(face: &str, a: f64, b: f64) -> Vec3 {
+    match face {
+        "f" => Vec3 { x: a, y: -b, z: 1.0 },
+        "r" => Vec3 { x: 1.0, y: -b, z: -a },
+        "b" => Vec3 { x: -a, y: -b, z: -1.0 },
+        "l" => Vec3 { x: -1.0, y: -b, z: a },
+        "u" => Vec3 { x: a, y: 1.0, z: b },
+        "d" => Vec3 { x: a, y: -1.0, z: -b },
+        _ => unreachable!("unknown cube face {face}"),
+    }
+}
+
+/// Bilinear sample of `source` at floating point coordinates, wrapping horizontally (the
+/// panorama is a full 360 turn) and clamping vertically
+fn sample_bilinear(source: &DynamicImage, u: f64, v: f64) -> Rgba<u8> {
+    let (width, height) = source.dimensions();
+    let wrap = |value: f64, size: u32| value.rem_euclid(size as f64);
+    let clamp = |value: f64, size: u32| value.max(0.0).min(size as f64 - 1.0);
+
+    let u = wrap(u - 0.5, width);
+    let v = clamp(v - 0.5, height);
+
+    let u0 = u.floor();
+    let v0 = v.floor();
+    let u_frac = u - u0;
+    let v_frac = v - v0;
+
+    let x0 = u0 as u32 % width;
+    let x1 = (x0 + 1) % width;
+    let y0 = v0.max(0.0) as u32;
+    let y1 = (y0 + 1).min(height - 1);
+
+    let p00 = source.get_pixel(x0, y0).0;
+    let p10 = source.get_pixel(x1, y0).0;
+    let p01 = source.get_pixel(x0, y1).0;
+    let p11 = source.get_pixel(x1, y1).0;
+
+    let mut out = [0u8; 4];
+    for c in 0..4 {
+        let top = p00[c] as f64 * (1.0 - u_frac) + p10[c] as f64 * u_frac;
+        let bottom = p01[c] as f64 * (1.0 - u_frac) + p11[c] as f64 * u_frac;
+        out[c] = (top * (1.0 - v_frac) + bottom * v_frac).round() as u8;
+    }
+    Rgba(out)
+}
+
+/// Render one cube face out of an equirectangular panorama
+fn render_face(source: &DynamicImage, face: &str, face_size: u32) -> DynamicImage {
+    let (width, height) = source.dimensions();
+    let mut buffer = image::RgbaImage::new(face_size, face_size);
+
+    for y in 0..face_size {
+        for x in 0..face_size {
+            let a = 2.0 * (x as f64 + 0.5) / face_size as f64 - 1.0;
+            let b = 2.0 * (y as f64 + 0.5) / face_size as f64 - 1.0;
+            let dir = face_direction(face, a, b).normalize();
+
+            let lon = dir.x.atan2(dir.z);
+            let lat = dir.y.asin();
+
+            let u = (lon + std::f64::consts::PI) / (2.0 * std::f64::consts::PI) * width as f64;
+            let v = (std::f64::consts::FRAC_PI_2 - lat) / std::f64::consts::PI * height as f64;
+
+            buffer.put_pixel(x, y, sample_bilinear(source, u, v));
+        }
+    }
+
+    DynamicImage::ImageRgba8(buffer)
+}
+
+/// Split an equirectangular panorama into the six cube faces, each `face_size x face_size`,
+/// named in the order of [`FACE_NAMES`] (`f`ront, `r`ight, `b`ack, `l`eft, `u`p, `d`own)
+pub fn build_cube_faces(source: &DynamicImage, face_size: u32) -> Vec<(&'static str, DynamicImage)> {
+    FACE_NAMES.iter().map(|&face| (face, render_face(source, face, face_size))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn face_direction_center_points_along_face_axis() {
+        let center = |face| {
+            let dir = face_direction(face, 0.0, 0.0);
+            (dir.x, dir.y, dir.z)
+        };
+        assert_eq!(center("f"), (0.0, 0.0, 1.0));
+        assert_eq!(center("r"), (1.0, 0.0, 0.0));
+        assert_eq!(center("b"), (0.0, 0.0, -1.0));
+        assert_eq!(center("l"), (-1.0, 0.0, 0.0));
+        assert_eq!(center("u"), (0.0, 1.0, 0.0));
+        assert_eq!(center("d"), (0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn sample_bilinear_exact_pixel_returns_unblended_color() {
+        let mut image = image::RgbaImage::new(2, 2);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([0, 255, 0, 255]));
+        image.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
+        image.put_pixel(1, 1, Rgba([255, 255, 0, 255]));
+        let source = DynamicImage::ImageRgba8(image);
+
+        assert_eq!(sample_bilinear(&source, 0.5, 0.5), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn sample_bilinear_wraps_horizontally_instead_of_clamping() {
+        // the panorama is a full 360 turn, so sampling just left of column 0 should wrap to the
+        // opposite edge column instead of clamping to column 0 again
+        let mut image = image::RgbaImage::new(2, 2);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([0, 255, 0, 255]));
+        image.put_pixel(0, 1, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 1, Rgba([0, 255, 0, 255]));
+        let source = DynamicImage::ImageRgba8(image);
+
+        assert_eq!(sample_bilinear(&source, -0.5, 0.5), Rgba([0, 255, 0, 255]));
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
@@ -6,9 +7,13 @@ use image::{
     DynamicImage,
     GenericImageView,
     ImageError,
-    imageops::{FilterType, resize},
-    io::Reader
+    ImageReader,
+    imageops::{FilterType, overlay, resize},
 };
+use rayon::prelude::*;
+use serde::Serialize;
+
+pub mod equirect;
 
 
 #[derive(thiserror::Error, Debug)]
@@ -21,10 +26,150 @@ pub enum TilingError {
     ImageError(#[from] ImageError),
     #[error("IO error: {0}")]
     IOError(#[from] std::io::Error),
+    #[error("Thread pool error: {0}")]
+    ThreadPoolError(#[from] rayon::ThreadPoolBuildError),
+    #[error("JSON serialization error: {0}")]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// Pannellum multires configuration, as consumed by Pannellum's multi-resolution viewer mode
+#[derive(Debug, Clone, Serialize)]
+pub struct MultiresConfig {
+    #[serde(rename = "basePath")]
+    pub base_path: String,
+    /// Path template for tiles, e.g. `%l/%s%y_%x` (level, face/prefix, row, column)
+    pub path: String,
+    /// Single fallback image shown to browsers without WebGL support, if the caller has one to
+    /// offer; `TileCreator` doesn't render one itself, so this is left unset unless the caller
+    /// supplies a path to an image it has placed there out of band
+    #[serde(rename = "fallbackPath", skip_serializing_if = "Option::is_none")]
+    pub fallback_path: Option<String>,
+    pub extension: String,
+    #[serde(rename = "tileResolution")]
+    pub tile_resolution: u32,
+    #[serde(rename = "maxLevel")]
+    pub max_level: u32,
+    #[serde(rename = "cubeResolution")]
+    pub cube_resolution: u32,
 }
 
 pub type MultiresResult<T, E = TilingError> = Result<T, E>;
 
+/// Decoded tiles of one pyramid level, keyed by `(row, col)`, kept around so the level above can
+/// be merged from the original pixels instead of re-decoding the (possibly lossy) encoded tile
+type TileCache = HashMap<(u32, u32), DynamicImage>;
+
+/// Width or height of a Deep Zoom level that is `shift` levels below the full-resolution image.
+/// Rounds up (rather than the naive floor-halving a plain `>> shift` would give) so
+/// non-power-of-two dimensions still reach exactly a 1x1 image at level 0.
+fn dzi_level_size(full_size: u32, shift: u32) -> u32 {
+    ((full_size as f64) / 2f64.powi(shift as i32)).ceil().max(1.0) as u32
+}
+
+/// Output pyramid layout: where a level's tiles live on disk and how they are named. This is
+/// what lets the same bottom-up pyramid builder in `TileCreator` feed either Pannellum's
+/// `level/prefix{row}_{col}` scheme or the Deep Zoom `prefix_files/level/{col}_{row}` scheme.
+pub trait TileLayout: std::fmt::Debug + Send + Sync {
+    /// Directory holding the tiles for the given pyramid level, in the layout's own numbering
+    /// (Pannellum: 1 = coarsest; Deep Zoom: 0 = the 1x1 image)
+    fn level_dir(&self, dest_path: &Path, level: u32) -> PathBuf;
+    /// File name (without directory) of the tile at row `i`, column `j`
+    fn tile_name(&self, i: u32, j: u32, extension: &str) -> String;
+}
+
+/// Pannellum's `level/prefix{row}_{col}.ext` tile layout
+#[derive(Debug, Clone)]
+pub struct PannellumLayout {
+    pub prefix: String,
+}
+
+impl TileLayout for PannellumLayout {
+    fn level_dir(&self, dest_path: &Path, level: u32) -> PathBuf {
+        dest_path.join(level.to_string())
+    }
+
+    fn tile_name(&self, i: u32, j: u32, extension: &str) -> String {
+        format!("{}{}_{}.{extension}", self.prefix, i, j)
+    }
+}
+
+/// Deep Zoom's `prefix_files/level/{col}_{row}.ext` tile layout, using native Deep Zoom level
+/// numbers directly: level 0 is a single 1x1 pixel image and the max level, `ceil(log2(max(width,
+/// height)))`, is the full-resolution image.
+#[derive(Debug, Clone)]
+pub struct DziLayout {
+    pub prefix: String,
+}
+
+impl TileLayout for DziLayout {
+    fn level_dir(&self, dest_path: &Path, level: u32) -> PathBuf {
+        dest_path.join(format!("{}_files", self.prefix)).join(level.to_string())
+    }
+
+    fn tile_name(&self, i: u32, j: u32, extension: &str) -> String {
+        format!("{}_{}.{extension}", j, i)
+    }
+}
+
+/// Selects which pyramid descriptor/layout `TileCreator` produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PyramidFormat {
+    /// Pannellum multires: `level/prefix{row}_{col}.ext` tiles plus a `config.json`
+    Pannellum,
+    /// Deep Zoom Image: `prefix_files/level/{col}_{row}.ext` tiles plus a `prefix.dzi` descriptor
+    Dzi,
+}
+
+/// Codec (and quality, where applicable) used to encode individual tiles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Jpeg { quality: u8 },
+    Png,
+    /// Lossless WebP; per `image`'s own `WebPEncoder` docs, only lossless encoding is supported
+    /// (lossy would require linking `libwebp` via the separate `webp` crate), so there is no
+    /// quality knob to take here
+    WebP,
+    Avif { quality: u8 },
+}
+
+impl OutputFormat {
+    /// File extension used for tiles written in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg { .. } => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Avif { .. } => "avif",
+        }
+    }
+
+    /// Encode `image` to `path` with this format's encoder, instead of relying on `save()`'s
+    /// extension-based format inference
+    fn encode(&self, image: &DynamicImage, path: &Path) -> MultiresResult<()> {
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+        match self {
+            OutputFormat::Jpeg { quality } => {
+                let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut writer, *quality);
+                encoder.encode_image(image)?;
+            }
+            OutputFormat::Png => {
+                let encoder = image::codecs::png::PngEncoder::new(&mut writer);
+                image.write_with_encoder(encoder)?;
+            }
+            OutputFormat::WebP => {
+                let encoder = image::codecs::webp::WebPEncoder::new_lossless(&mut writer);
+                image.write_with_encoder(encoder)?;
+            }
+            OutputFormat::Avif { quality } => {
+                let encoder = image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut writer, 4, *quality);
+                image.write_with_encoder(encoder)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// A tile creator, this struct and associated functions implement the Multires tiler
 #[derive(Debug)]
 pub struct TileCreator {
@@ -40,30 +185,44 @@ pub struct TileCreator {
     y_size: u32,
     /// total number of levels of tiles
     levels: u32,
-    /// select png image format
-    png: bool,
+    /// codec (and quality) used to encode tiles
+    output_format: OutputFormat,
     /// Prefix of tile filename
     prefix: String,
+    /// number of worker threads used to generate tiles
+    threads: usize,
+    /// output pyramid format (tile layout + descriptor)
+    format: PyramidFormat,
 }
 
 
 impl TileCreator {
     pub fn new_from_image_path(
-        image_path: PathBuf, dest_path: PathBuf, tile_size: u32, png: bool
+        image_path: PathBuf, dest_path: PathBuf, tile_size: u32, output_format: OutputFormat, threads: usize, format: PyramidFormat
     ) -> MultiresResult<Self> {
         let file = File::open(image_path.clone())?;
         let reader = BufReader::new(file);
-        let mut image_reader = Reader::new(reader).with_guessed_format()?;
+        let mut image_reader = ImageReader::new(reader).with_guessed_format()?;
         image_reader.no_limits();
         let im = image_reader.decode()?;
-        let (x_size, y_size) = im.dimensions();
 
         let prefix = Path::new(image_path.file_name().unwrap()).file_stem().unwrap().to_str().unwrap().to_string();
 
+        Self::new_from_image(im, prefix, dest_path, tile_size, output_format, threads, format)
+    }
+
+    /// Build a `TileCreator` from an already-decoded image (e.g. one cube face produced by
+    /// [`crate::equirect::build_cube_faces`]) instead of reading one from disk
+    pub fn new_from_image(
+        im: DynamicImage, prefix: String, dest_path: PathBuf, tile_size: u32, output_format: OutputFormat,
+        threads: usize, format: PyramidFormat
+    ) -> MultiresResult<Self> {
+        let (x_size, y_size) = im.dimensions();
+
         let tile_size = tile_size.min(x_size).min(y_size);
         let size = x_size.min(y_size);
         let mut levels: u32 = (size as f64 / tile_size as f64).log2().ceil() as u32 + 1;
-        if (size as f64 / 2u32.pow(levels - 2) as f64).round() as u32 == tile_size {
+        if levels >= 2 && (size as f64 / 2u32.pow(levels - 2) as f64).round() as u32 == tile_size {
             levels -= 1  // Handle edge case
         }
 
@@ -74,51 +233,441 @@ impl TileCreator {
             x_size,
             y_size,
             levels,
-            png,
+            output_format,
             prefix,
+            threads,
+            format,
         })
     }
 
-    /// Create Multires tiles
+    /// Build the tile layout for the configured output format
+    fn layout(&self) -> Box<dyn TileLayout> {
+        match self.format {
+            PyramidFormat::Pannellum => Box::new(PannellumLayout { prefix: self.prefix.clone() }),
+            PyramidFormat::Dzi => Box::new(DziLayout { prefix: self.prefix.clone() }),
+        }
+    }
+
+    /// True Deep Zoom max level: `ceil(log2(max(width, height)))`. Level 0 is a single 1x1 pixel
+    /// image; this level is the full-resolution image.
+    fn dzi_max_level(&self) -> u32 {
+        (self.x_size.max(self.y_size) as f64).log2().ceil() as u32
+    }
+
+    /// Path of an individual tile file
+    fn tile_path(&self, layout: &dyn TileLayout, level: u32, i: u32, j: u32, extension: &str) -> PathBuf {
+        layout.level_dir(&self.dest_path, level).join(layout.tile_name(i, j, extension))
+    }
+
+    /// Write the finest level (the full-resolution image) by cropping tiles directly out of the
+    /// source image, dispatching each tile to the given thread pool. Returns the cropped tiles
+    /// keyed by `(row, col)`, so the level above can merge from these pixels instead of
+    /// re-decoding the encoded files.
+    fn write_finest_level(&self, pool: &rayon::ThreadPool, layout: &dyn TileLayout, level: u32, extension: &str) -> MultiresResult<TileCache> {
+        std::fs::create_dir_all(layout.level_dir(&self.dest_path, level))?;
+
+        let x_tiles = (self.x_size as f64 / self.tile_size as f64).ceil() as u32;
+        let y_tiles = (self.y_size as f64 / self.tile_size as f64).ceil() as u32;
+        let coords: Vec<(u32, u32)> = (0..y_tiles).flat_map(|i| (0..x_tiles).map(move |j| (i, j))).collect();
+
+        pool.install(|| {
+            coords.into_par_iter().map(|(i, j)| -> MultiresResult<((u32, u32), DynamicImage)> {
+                let left = j * self.tile_size;
+                let upper = i * self.tile_size;
+                let width = if left + self.tile_size >= self.x_size {
+                    self.x_size - left
+                } else {
+                    self.tile_size
+                };
+                let height = if upper + self.tile_size >= self.y_size {
+                    self.y_size - upper
+                } else {
+                    self.tile_size
+                };
+                let tile_image = self.image.crop_imm(left, upper, width, height);
+                self.output_format.encode(&tile_image, &self.tile_path(layout, level, i, j, extension))?;
+                Ok(((i, j), tile_image))
+            }).collect::<MultiresResult<Vec<_>>>()
+        }).map(TileCache::from_iter)
+    }
+
+    /// Write a coarser level by merging the four children tiles of the level below, rather than
+    /// resizing the whole source image again, dispatching each tile to the given thread pool.
+    /// `children` holds the decoded pixels of the level below, keyed by `(row, col)`, so a lossy
+    /// codec never has to be re-decoded off disk to build the pyramid above it. Returns this
+    /// level's own tiles for the next (coarser) call to consume in turn.
+    fn write_merged_level(
+        &self, pool: &rayon::ThreadPool, layout: &dyn TileLayout, level: u32, level_size: (u32, u32), extension: &str,
+        children: &TileCache,
+    ) -> MultiresResult<TileCache> {
+        std::fs::create_dir_all(layout.level_dir(&self.dest_path, level))?;
+
+        let (x_size, y_size) = level_size;
+        let tile_size = self.tile_size;
+        let x_tiles = (x_size as f64 / tile_size as f64).ceil() as u32;
+        let y_tiles = (y_size as f64 / tile_size as f64).ceil() as u32;
+        let coords: Vec<(u32, u32)> = (0..y_tiles).flat_map(|i| (0..x_tiles).map(move |j| (i, j))).collect();
+
+        pool.install(|| {
+            coords.into_par_iter().map(|(i, j)| -> MultiresResult<((u32, u32), DynamicImage)> {
+                let mut canvas = DynamicImage::new_rgba8(2 * tile_size, 2 * tile_size);
+                let offsets = [
+                    (2 * i, 2 * j, 0, 0),
+                    (2 * i, 2 * j + 1, tile_size, 0),
+                    (2 * i + 1, 2 * j, 0, tile_size),
+                    (2 * i + 1, 2 * j + 1, tile_size, tile_size),
+                ];
+                for (ci, cj, x_off, y_off) in offsets {
+                    if let Some(child) = children.get(&(ci, cj)) {
+                        overlay(&mut canvas, child, x_off as i64, y_off as i64);
+                    }
+                }
+
+                let resized = DynamicImage::ImageRgba8(resize(&canvas, tile_size, tile_size, FilterType::Triangle));
+
+                let left = j * tile_size;
+                let upper = i * tile_size;
+                let width = if left + tile_size >= x_size { x_size - left } else { tile_size };
+                let height = if upper + tile_size >= y_size { y_size - upper } else { tile_size };
+                let tile_image = resized.crop_imm(0, 0, width, height);
+                self.output_format.encode(&tile_image, &self.tile_path(layout, level, i, j, extension))?;
+                Ok(((i, j), tile_image))
+            }).collect::<MultiresResult<Vec<_>>>()
+        }).map(TileCache::from_iter)
+    }
+
+    /// Create Multires tiles, building the pyramid bottom-up: the finest level is cropped
+    /// directly from the source, then each coarser level is assembled purely from the four
+    /// children tiles below it instead of re-resizing the whole source image every level. The
+    /// children are merged from the in-memory pixels returned by the level below, not read back
+    /// from the encoded tile files, so lossy codecs don't compound recompression artifacts up
+    /// the pyramid. Tiles within a level are generated in parallel over a pool of `self.threads`
+    /// workers; each level transition acts as a synchronization barrier since a level needs every
+    /// tile of the level below it to already be built.
+    ///
+    /// Pannellum numbers levels internally (1 = coarsest, `self.levels` = finest) and only needs
+    /// that range. Deep Zoom viewers additionally require the full pyramid down to the 1x1 image
+    /// at level 0, so DZI output walks every native Deep Zoom level instead.
     pub fn create_tiles(&mut self) -> MultiresResult<()> {
-        let mut x_size = self.x_size;
-        let mut y_size = self.y_size;
-        for level in (1..=self.levels).rev() {
-            let p = self.dest_path.join(level.to_string());
-            std::fs::create_dir_all(&p)?;
-
-            let x_tiles = (x_size as f64 / self.tile_size as f64).ceil() as u32;
-            let y_tiles = (y_size as f64 / self.tile_size as f64).ceil() as u32;
-            if level < self.levels {
-                self.image = image::DynamicImage::ImageRgba8(
-                    resize(&self.image, x_size, y_size, FilterType::Triangle)
-                );
-            }
-            for i in 0..y_tiles {
-                for j in 0..x_tiles {
-                    let left = j * self.tile_size;
-                    let upper = i * self.tile_size;
-                    let width = if left + self.tile_size >= self.x_size {
-                        self.x_size - left
-                    } else {
-                        self.tile_size
-                    };
-                    let height = if upper + self.tile_size >= self.y_size {
-                        self.y_size - upper
-                    } else {
-                        self.tile_size
-                    };
-                    let tile_image = self.image.crop_imm(left, upper, width, height);
-                    let extension = if self.png { "png" } else { "jpg" };
-
-                    let tile_path = self.dest_path.join(level.to_string()).join(format!("{}{}_{}.{extension}", self.prefix, i, j));
-                    tile_image.save(tile_path)?;
+        let extension = self.output_format.extension();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(self.threads).build()?;
+        let layout = self.layout();
+
+        match self.format {
+            PyramidFormat::Pannellum => {
+                let mut children = self.write_finest_level(&pool, layout.as_ref(), self.levels, extension)?;
+
+                let mut x_size = self.x_size;
+                let mut y_size = self.y_size;
+                for level in (1..self.levels).rev() {
+                    x_size /= 2;
+                    y_size /= 2;
+                    children = self.write_merged_level(&pool, layout.as_ref(), level, (x_size, y_size), extension, &children)?;
                 }
             }
+            PyramidFormat::Dzi => {
+                let max_level = self.dzi_max_level();
+                let mut children = self.write_finest_level(&pool, layout.as_ref(), max_level, extension)?;
 
-            x_size = x_size / 2;
-            y_size = y_size / 2;
+                for level in (0..max_level).rev() {
+                    let shift = max_level - level;
+                    let level_size = (dzi_level_size(self.x_size, shift), dzi_level_size(self.y_size, shift));
+                    children = self.write_merged_level(&pool, layout.as_ref(), level, level_size, extension, &children)?;
+                }
+            }
         }
         Ok(())
     }
+
+    /// Build the Pannellum multires config describing this pyramid, and write it as
+    /// `config.json` in the destination directory so it can be dropped straight into a
+    /// Pannellum viewer. `fallback_path` is passed straight through to the config's
+    /// `fallbackPath` and is left unset if `None`; `TileCreator` doesn't render a fallback image
+    /// itself, so it's on the caller to have one in place at the path it names.
+    pub fn write_multires_config(&self, fallback_path: Option<String>) -> MultiresResult<MultiresConfig> {
+        let extension = self.output_format.extension();
+        let config = MultiresConfig {
+            base_path: self.dest_path.to_string_lossy().into_owned(),
+            path: format!("%l/{}%y_%x", self.prefix),
+            fallback_path,
+            extension: extension.to_string(),
+            tile_resolution: self.tile_size,
+            max_level: self.levels,
+            cube_resolution: self.x_size.min(self.y_size),
+        };
+
+        let config_path = self.dest_path.join("config.json");
+        let file = std::fs::File::create(config_path)?;
+        serde_json::to_writer_pretty(file, &config)?;
+
+        Ok(config)
+    }
+
+    /// Write a single combined Pannellum multires config describing a cubemap pyramid made of
+    /// several `TileCreator`-built faces sharing one `dest_path`, using Pannellum's `%s` face
+    /// placeholder instead of a fixed prefix. `fallback_path` behaves as in
+    /// [`Self::write_multires_config`].
+    pub fn write_combined_multires_config(
+        dest_path: &Path, tile_size: u32, levels: u32, cube_resolution: u32, output_format: OutputFormat,
+        fallback_path: Option<String>,
+    ) -> MultiresResult<MultiresConfig> {
+        let config = MultiresConfig {
+            base_path: dest_path.to_string_lossy().into_owned(),
+            path: "%l/%s%y_%x".to_string(),
+            fallback_path,
+            extension: output_format.extension().to_string(),
+            tile_resolution: tile_size,
+            max_level: levels,
+            cube_resolution,
+        };
+
+        let config_path = dest_path.join("config.json");
+        let file = std::fs::File::create(config_path)?;
+        serde_json::to_writer_pretty(file, &config)?;
+
+        Ok(config)
+    }
+
+    /// Size in pixels of individual tiles
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// Total number of pyramid levels
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    /// Total number of pyramid levels actually written to disk by [`Self::create_tiles`]: for
+    /// Pannellum this is `self.levels` (its own internal numbering), for DZI it's the full
+    /// `0..=dzi_max_level` pyramid depth
+    pub fn levels_written(&self) -> u32 {
+        match self.format {
+            PyramidFormat::Pannellum => self.levels,
+            PyramidFormat::Dzi => self.dzi_max_level() + 1,
+        }
+    }
+
+    /// Write the Deep Zoom Image descriptor (`<prefix>.dzi`) describing this pyramid, for
+    /// viewers such as OpenSeadragon
+    pub fn write_dzi_descriptor(&self) -> MultiresResult<PathBuf> {
+        let extension = self.output_format.extension();
+        let xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" TileSize=\"{}\" Overlap=\"0\" Format=\"{}\">\n\
+             \t<Size Width=\"{}\" Height=\"{}\" />\n\
+             </Image>\n",
+            self.tile_size, extension, self.x_size, self.y_size,
+        );
+
+        let dzi_path = self.dest_path.join(format!("{}.dzi", self.prefix));
+        std::fs::write(&dzi_path, xml)?;
+
+        Ok(dzi_path)
+    }
+}
+
+/// Split an equirectangular panorama into its six cube faces and tile each one. For
+/// [`PyramidFormat::Pannellum`] this writes a single combined `config.json` covering all faces;
+/// for [`PyramidFormat::Dzi`] each face has no shared descriptor format, so each gets its own
+/// `<face>.dzi` instead.
+pub fn tile_equirectangular(
+    image_path: PathBuf, dest_path: PathBuf, face_size: u32, tile_size: u32, output_format: OutputFormat, threads: usize,
+    format: PyramidFormat,
+) -> MultiresResult<()> {
+    let file = File::open(&image_path)?;
+    let reader = BufReader::new(file);
+    let mut image_reader = ImageReader::new(reader).with_guessed_format()?;
+    image_reader.no_limits();
+    let panorama = image_reader.decode()?;
+
+    let faces = equirect::build_cube_faces(&panorama, face_size);
+
+    let mut tile_size_used = tile_size;
+    let mut levels_used = 1;
+    for (face_name, face_image) in faces {
+        let face_dest = dest_path.clone();
+        let mut tile_creator = TileCreator::new_from_image(
+            face_image, face_name.to_string(), face_dest, tile_size, output_format, threads, format,
+        )?;
+        tile_creator.create_tiles()?;
+        tile_size_used = tile_creator.tile_size();
+        levels_used = tile_creator.levels();
+
+        if format == PyramidFormat::Dzi {
+            tile_creator.write_dzi_descriptor()?;
+        }
+    }
+
+    if format == PyramidFormat::Pannellum {
+        TileCreator::write_combined_multires_config(&dest_path, tile_size_used, levels_used, face_size, output_format, None)?;
+    }
+    Ok(())
+}
+
+/// Per-image pyramid depth, for [`BatchSummary`]
+#[derive(Debug, Clone)]
+pub struct ImageLevels {
+    pub prefix: String,
+    pub levels: u32,
+}
+
+/// Stats summary printed after batch/folder tiling, so users get a quick sanity check on
+/// pyramid sizes without having to walk the output tree themselves
+#[derive(Debug, Clone)]
+pub struct BatchSummary {
+    pub images_processed: usize,
+    pub total_tiles: u64,
+    pub total_bytes: u64,
+    pub levels_per_image: Vec<ImageLevels>,
+}
+
+/// Recursively count tile files with the given extension under `path`, and sum their sizes
+fn tally_tiles(path: &Path, extension: &str) -> MultiresResult<(u64, u64)> {
+    let mut count = 0u64;
+    let mut bytes = 0u64;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+        if entry_path.is_dir() {
+            let (nested_count, nested_bytes) = tally_tiles(&entry_path, extension)?;
+            count += nested_count;
+            bytes += nested_bytes;
+        } else if entry_path.extension().and_then(|ext| ext.to_str()) == Some(extension) {
+            count += 1;
+            bytes += entry.metadata()?.len();
+        }
+    }
+    Ok((count, bytes))
+}
+
+/// Tile every image file found directly under `image_dir`, one output subfolder per image
+/// (named after its file stem) under `dest_path`, dispatching images across a pool of
+/// `threads` workers. Returns a summary of how much was produced.
+pub fn tile_directory(
+    image_dir: PathBuf, dest_path: PathBuf, tile_size: u32, output_format: OutputFormat, threads: usize, format: PyramidFormat
+) -> MultiresResult<BatchSummary> {
+    let mut image_paths: Vec<PathBuf> = std::fs::read_dir(&image_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    image_paths.sort();
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()?;
+    let extension = output_format.extension();
+
+    let results: Vec<MultiresResult<(String, u32, u64, u64)>> = pool.install(|| {
+        image_paths.into_par_iter().map(|image_path| -> MultiresResult<(String, u32, u64, u64)> {
+            let prefix = Path::new(image_path.file_name().unwrap()).file_stem().unwrap().to_str().unwrap().to_string();
+            let image_dest = dest_path.join(&prefix);
+            std::fs::create_dir_all(&image_dest)?;
+
+            let mut tile_creator = TileCreator::new_from_image_path(
+                image_path, image_dest.clone(), tile_size, output_format, 1, format,
+            )?;
+            tile_creator.create_tiles()?;
+            match format {
+                PyramidFormat::Pannellum => { tile_creator.write_multires_config(None)?; },
+                PyramidFormat::Dzi => { tile_creator.write_dzi_descriptor()?; },
+            }
+
+            let (tiles, bytes) = tally_tiles(&image_dest, extension)?;
+            Ok((prefix, tile_creator.levels_written(), tiles, bytes))
+        }).collect()
+    });
+
+    let mut summary = BatchSummary {
+        images_processed: 0,
+        total_tiles: 0,
+        total_bytes: 0,
+        levels_per_image: Vec::new(),
+    };
+    for result in results {
+        let (prefix, levels, tiles, bytes) = result?;
+        summary.images_processed += 1;
+        summary.total_tiles += tiles;
+        summary.total_bytes += bytes;
+        summary.levels_per_image.push(ImageLevels { prefix, levels });
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dzi_level_size_reaches_exactly_one_at_the_top_shift() {
+        // 300 is not a power of two; naive floor-halving would bottom out at 0, not 1
+        assert_eq!(dzi_level_size(300, 9), 1);
+        assert_eq!(dzi_level_size(300, 0), 300);
+    }
+
+    #[test]
+    fn dzi_level_size_rounds_up_for_odd_dimensions() {
+        assert_eq!(dzi_level_size(3, 1), 2);
+        assert_eq!(dzi_level_size(5, 2), 2);
+    }
+
+    #[test]
+    fn dzi_max_level_matches_ceil_log2_of_the_longest_side() {
+        let image = DynamicImage::new_rgba8(300, 200);
+        let tile_creator = TileCreator::new_from_image(
+            image, "t".to_string(), PathBuf::from("/tmp"), 64, OutputFormat::Png, 1, PyramidFormat::Dzi,
+        ).unwrap();
+
+        // ceil(log2(300)) == 9
+        assert_eq!(tile_creator.dzi_max_level(), 9);
+    }
+
+    #[test]
+    fn merged_level_is_the_2x_downscale_of_its_four_children() {
+        use image::Rgba;
+
+        // four flat-colored 8x8 quadrants (wide enough that the downscale filter's support
+        // doesn't reach across a quadrant boundary), so a seam/orientation bug in the child
+        // placement shows up as a wrong corner color in the merged tile instead of being lost
+        // in blending noise
+        let mut source = image::RgbaImage::new(16, 16);
+        for y in 0..16u32 {
+            for x in 0..16u32 {
+                let color = match (x < 8, y < 8) {
+                    (true, true) => Rgba([255, 0, 0, 255]),
+                    (false, true) => Rgba([0, 255, 0, 255]),
+                    (true, false) => Rgba([0, 0, 255, 255]),
+                    (false, false) => Rgba([255, 255, 0, 255]),
+                };
+                source.put_pixel(x, y, color);
+            }
+        }
+
+        let dest = std::env::temp_dir().join(format!("f2m-test-merge-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dest);
+
+        let tile_creator = TileCreator::new_from_image(
+            DynamicImage::ImageRgba8(source), "t".to_string(), dest.clone(), 8, OutputFormat::Png, 1, PyramidFormat::Pannellum,
+        ).unwrap();
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let layout = tile_creator.layout();
+        let extension = tile_creator.output_format.extension();
+
+        let children = tile_creator.write_finest_level(&pool, layout.as_ref(), tile_creator.levels, extension).unwrap();
+        assert_eq!(children.get(&(0, 0)).unwrap().get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(children.get(&(0, 1)).unwrap().get_pixel(0, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(children.get(&(1, 0)).unwrap().get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(children.get(&(1, 1)).unwrap().get_pixel(0, 0), Rgba([255, 255, 0, 255]));
+
+        let merged = tile_creator.write_merged_level(&pool, layout.as_ref(), 1, (8, 8), extension, &children).unwrap();
+        let coarse_tile = merged.get(&(0, 0)).unwrap();
+
+        assert_eq!(coarse_tile.dimensions(), (8, 8));
+        assert_eq!(coarse_tile.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(coarse_tile.get_pixel(7, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(coarse_tile.get_pixel(0, 7), Rgba([0, 0, 255, 255]));
+        assert_eq!(coarse_tile.get_pixel(7, 7), Rgba([255, 255, 0, 255]));
+
+        let _ = std::fs::remove_dir_all(&dest);
+    }
 }
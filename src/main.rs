@@ -1,20 +1,31 @@
-use std::{env, fs};
+use std::fs;
 use std::path::PathBuf;
 use std::process;
 
 use clap::{Arg, ArgAction, command};
 
-use image2multires::TileCreator;
+use image2multires::{tile_directory, tile_equirectangular, OutputFormat, PyramidFormat, TileCreator};
 
 
 fn main() {
     let matches = command!()
         .arg(
-            Arg::new("png")
-            .short('p')
-            .long("png")
-            .action(ArgAction::SetTrue)
-            .help("Set tile image format to png instead of default jpg")
+            Arg::new("codec")
+            .short('c')
+            .long("codec")
+            .action(ArgAction::Set)
+            .default_value("jpeg")
+            .value_parser(["jpeg", "png", "webp", "avif"])
+            .help("Tile image codec")
+		)
+        .arg(
+            Arg::new("quality")
+            .short('q')
+            .long("quality")
+            .action(ArgAction::Set)
+            .default_value("85")
+            .value_parser(clap::value_parser!(u8))
+            .help("Quality used to encode lossy tile codecs (jpeg, avif); ignored for png and webp")
 		)
         .arg(
             Arg::new("tile-size")
@@ -30,16 +41,81 @@ fn main() {
             .short('d')
             .long("directory")
             .default_value("output")
-            .help("Set output directory of tile image files")
+            .help("Output directory of tile image files; with --batch, the input directory of images to tile instead")
+        )
+        .arg(
+            Arg::new("output-directory")
+            .short('o')
+            .long("output-directory")
+            .default_value("output")
+            .help("Output directory of tile image files, with --batch")
+        )
+        .arg(
+            Arg::new("threads")
+            .short('t')
+            .long("threads")
+            .action(ArgAction::Set)
+            .value_parser(clap::value_parser!(u16))
+            .help("Number of worker threads used to generate tiles (defaults to available parallelism)")
+        )
+        .arg(
+            Arg::new("format")
+            .short('f')
+            .long("format")
+            .action(ArgAction::Set)
+            .default_value("pannellum")
+            .value_parser(["pannellum", "dzi"])
+            .help("Pyramid output format: pannellum multires or Deep Zoom Image (dzi)")
+        )
+        .arg(
+            Arg::new("equirectangular")
+            .short('e')
+            .long("equirectangular")
+            .action(ArgAction::SetTrue)
+            .help("Treat the input image as an equirectangular panorama and split it into six cube faces before tiling")
+        )
+        .arg(
+            Arg::new("face-size")
+            .long("facesize")
+            .action(ArgAction::Set)
+            .default_value("2048")
+            .value_parser(clap::value_parser!(u32))
+            .help("Edge size in pixels of each generated cube face, with --equirectangular")
+        )
+        .arg(
+            Arg::new("batch")
+            .short('b')
+            .long("batch")
+            .action(ArgAction::SetTrue)
+            .help("Treat --directory as a folder of images and tile each one in parallel, under its own subfolder of --output-directory")
         )
         .arg(Arg::new("image"))
         .get_matches();
 
-    let png_flag = *matches.get_one::<bool>("png").unwrap();
+    let quality = *matches.get_one::<u8>("quality").unwrap();
+    let output_format = match matches.get_one::<String>("codec").unwrap().as_str() {
+        "png" => OutputFormat::Png,
+        "webp" => OutputFormat::WebP,
+        "avif" => OutputFormat::Avif { quality },
+        _ => OutputFormat::Jpeg { quality },
+    };
     let tile_size: u16 = *matches.get_one::<u16>("tile-size").unwrap();
-    let image_path = PathBuf::from(matches.get_one::<String>("image").unwrap());
+    let threads: usize = match matches.get_one::<u16>("threads") {
+        Some(threads) => *threads as usize,
+        None => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+    };
+    let format = match matches.get_one::<String>("format").unwrap().as_str() {
+        "dzi" => PyramidFormat::Dzi,
+        _ => PyramidFormat::Pannellum,
+    };
+    let batch = *matches.get_one::<bool>("batch").unwrap();
+    let directory_arg = PathBuf::from(matches.get_one::<String>("directory").unwrap());
+    let (image_path, directory) = if batch {
+        (directory_arg, PathBuf::from(matches.get_one::<String>("output-directory").unwrap()))
+    } else {
+        (PathBuf::from(matches.get_one::<String>("image").unwrap()), directory_arg)
+    };
 
-    let directory = PathBuf::from(matches.get_one::<String>("directory").unwrap());
     if !directory.exists() {
         println!("creating directory {}", directory.to_str().unwrap());
         if let Err(error) = fs::create_dir_all(directory.clone()) {
@@ -48,10 +124,41 @@ fn main() {
         }
     }
 
-    match TileCreator::new_from_image_path(image_path, directory, tile_size as u32, png_flag) {
+    let equirectangular = *matches.get_one::<bool>("equirectangular").unwrap();
+    if equirectangular {
+        let face_size = *matches.get_one::<u32>("face-size").unwrap();
+        if let Err(e) = tile_equirectangular(image_path, directory, face_size, tile_size as u32, output_format, threads, format) {
+            eprintln!("{}", e);
+        }
+        return;
+    }
+
+    if batch {
+        match tile_directory(image_path, directory, tile_size as u32, output_format, threads, format) {
+            Ok(summary) => {
+                println!("processed {} image(s)", summary.images_processed);
+                println!("wrote {} tile(s), {} bytes total", summary.total_tiles, summary.total_bytes);
+                for image_levels in &summary.levels_per_image {
+                    println!("  {}: {} level(s)", image_levels.prefix, image_levels.levels);
+                }
+            },
+            Err(e) => { eprintln!("{}", e); }
+        }
+        return;
+    }
+
+    match TileCreator::new_from_image_path(image_path, directory, tile_size as u32, output_format, threads, format) {
         Ok(mut ic) => {
             match ic.create_tiles() {
-                Ok(_) => {},
+                Ok(_) => {
+                    let descriptor = match format {
+                        PyramidFormat::Pannellum => ic.write_multires_config(None).map(|_| ()),
+                        PyramidFormat::Dzi => ic.write_dzi_descriptor().map(|_| ()),
+                    };
+                    if let Err(e) = descriptor {
+                        eprintln!("{}", e);
+                    }
+                },
                 Err(e) => { eprintln!("{}", e); }
             }
         },